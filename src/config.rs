@@ -0,0 +1,89 @@
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use glob::Pattern;
+use serde::Deserialize;
+use std::fs;
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub exclude: Vec<String>,
+    #[serde(rename = "opener")]
+    pub openers: Vec<OpenerRule>,
+    pub editor: EditorConfig,
+    pub cleanup: CleanupConfig,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EditorConfig {
+    pub layout: Option<Layout>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CleanupConfig {
+    pub auto: bool,
+    pub background: bool,
+    pub grace_days: u32,
+    pub unavailable_days: u32,
+    pub check_hours: u32,
+}
+
+impl Default for CleanupConfig {
+    fn default() -> Self {
+        Self {
+            auto: true,
+            background: false,
+            grace_days: 30,
+            unavailable_days: 180,
+            check_hours: 24,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    Tabs,
+    Splits,
+    Vsplits,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OpenerRule {
+    pub glob: Option<String>,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    pub mime: Option<String>,
+    pub command: String,
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        let dirs = ProjectDirs::from("", "", "texoxide")
+            .context("Could not determine project directories")?;
+        let path = dirs.config_dir().join("config.toml");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn exclude_patterns(&self) -> Vec<Pattern> {
+        self.exclude
+            .iter()
+            .filter_map(|glob| Pattern::new(glob).ok())
+            .collect()
+    }
+
+    pub fn is_excluded(&self, path: &str) -> bool {
+        self.exclude_patterns()
+            .iter()
+            .any(|glob| glob.matches(path))
+    }
+}
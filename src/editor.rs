@@ -0,0 +1,314 @@
+use crate::config::Layout;
+use anyhow::{Context, Result};
+use std::{
+    env,
+    io::{stdin, stdout, IsTerminal},
+    path::Path,
+    process::Command,
+};
+
+#[cfg(windows)]
+const FALLBACK_EDITORS: [&str; 1] = ["notepad"];
+#[cfg(not(windows))]
+const FALLBACK_EDITORS: [&str; 6] = ["nvim", "vim", "vi", "nano", "hx", "emacs"];
+
+pub struct Editor {
+    program: String,
+    args: Vec<String>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Position {
+    pub line: u32,
+    pub column: Option<u32>,
+}
+
+pub struct Location {
+    pub path: String,
+    pub position: Option<Position>,
+}
+
+/// Splits a trailing `:line` or `:line:col` off `arg`, as printed by compilers.
+pub fn split_position(arg: &str) -> (&str, Option<Position>) {
+    let trimmed = arg.strip_suffix(':').unwrap_or(arg);
+    let Some((rest, last)) = trimmed.rsplit_once(':') else {
+        return (arg, None);
+    };
+    let Ok(last) = last.parse::<u32>() else {
+        return (arg, None);
+    };
+
+    if let Some((path, line)) = rest.rsplit_once(':') {
+        if let Ok(line) = line.parse() {
+            let position = Position {
+                line,
+                column: Some(last),
+            };
+            return (path, Some(position));
+        }
+    }
+    let position = Position {
+        line: last,
+        column: None,
+    };
+    (rest, Some(position))
+}
+
+/// Strips a position from the last keyword, so `main.rs:42` searches for `main.rs`.
+pub fn strip_position(keywords: &[String]) -> (Vec<String>, Option<Position>) {
+    let mut keywords = keywords.to_vec();
+    let mut position = None;
+    if let Some(last) = keywords.last_mut() {
+        let (rest, pos) = split_position(last);
+        if pos.is_some() {
+            *last = rest.to_string();
+            position = pos;
+        }
+    }
+    keywords.retain(|k| !k.is_empty());
+    (keywords, position)
+}
+
+impl Location {
+    pub fn new(path: &str, position: Option<Position>) -> Self {
+        Self {
+            path: path.to_string(),
+            position,
+        }
+    }
+
+    pub fn parse(arg: &str) -> Self {
+        if Path::new(arg).exists() {
+            return Self::new(arg, None);
+        }
+        let (path, position) = split_position(arg);
+        Self::new(path, position)
+    }
+}
+
+fn editor_name(editor: &str) -> &str {
+    Path::new(editor)
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or(editor)
+}
+
+fn editor_args(editor: &str, location: &Location) -> Vec<String> {
+    let path = location.path.clone();
+    let Some(Position { line, column }) = location.position else {
+        return vec![path];
+    };
+
+    match (editor_name(editor), column) {
+        ("vi" | "vim" | "nvim" | "gvim" | "mvim", Some(column)) => {
+            vec![format!("+call cursor({line}, {column})"), path]
+        }
+        ("code" | "code-insiders" | "codium" | "cursor", _) => {
+            vec![
+                "-g".to_string(),
+                format!("{path}:{line}:{}", column.unwrap_or(1)),
+            ]
+        }
+        ("hx" | "helix" | "subl" | "zed", _) => {
+            vec![format!("{path}:{line}:{}", column.unwrap_or(1))]
+        }
+        ("emacs" | "emacsclient" | "kak" | "micro", Some(column)) => {
+            vec![format!("+{line}:{column}"), path]
+        }
+        ("nano", Some(column)) => vec![format!("+{line},{column}"), path],
+        (
+            "vi" | "vim" | "nvim" | "gvim" | "mvim" | "emacs" | "emacsclient" | "kak" | "micro"
+            | "nano" | "gedit" | "joe" | "ne",
+            _,
+        ) => vec![format!("+{line}"), path],
+        _ => vec![path],
+    }
+}
+
+fn layout_args(editor: &str, layout: Layout) -> &'static [&'static str] {
+    match (editor_name(editor), layout) {
+        ("vi" | "vim" | "nvim" | "gvim" | "mvim", Layout::Tabs) => &["-p"],
+        ("vi" | "vim" | "nvim" | "gvim" | "mvim", Layout::Splits) => &["-o"],
+        ("vi" | "vim" | "nvim" | "gvim" | "mvim", Layout::Vsplits) => &["-O"],
+        ("hx" | "helix", Layout::Splits) => &["--hsplit"],
+        ("hx" | "helix", Layout::Vsplits) => &["--vsplit"],
+        _ => &[],
+    }
+}
+
+fn in_path(program: &str) -> bool {
+    let Some(paths) = env::var_os("PATH") else {
+        return false;
+    };
+    env::split_paths(&paths).any(|dir| {
+        let candidate = dir.join(program);
+        candidate.is_file() || (cfg!(windows) && candidate.with_extension("exe").is_file())
+    })
+}
+
+impl Editor {
+    /// Picks the editor from `$TEXOXIDE_EDITOR`, then `$VISUAL` and `$EDITOR`, with
+    /// `$VISUAL` only taking precedence when attached to a terminal. Falls back to
+    /// the first editor found on `$PATH`.
+    pub fn from_env() -> Result<Self> {
+        let vars = if stdin().is_terminal() && stdout().is_terminal() {
+            ["TEXOXIDE_EDITOR", "VISUAL", "EDITOR"]
+        } else {
+            ["TEXOXIDE_EDITOR", "EDITOR", "VISUAL"]
+        };
+
+        for var in vars {
+            let Ok(value) = env::var(var) else {
+                continue;
+            };
+            if value.trim().is_empty() {
+                continue;
+            }
+            return Self::parse(&value).with_context(|| format!("Could not parse ${var}: {value}"));
+        }
+
+        FALLBACK_EDITORS
+            .iter()
+            .find(|program| in_path(program))
+            .map(|program| Self {
+                program: (*program).to_string(),
+                args: Vec::new(),
+            })
+            .context("No editor found, set $EDITOR")
+    }
+
+    /// Splits an editor command into the program and its arguments. A path to an
+    /// existing file is used as is, so unquoted spaces and backslashes in it are kept.
+    /// Windows commands are only split on whitespace, backslashes separate paths there.
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let mut words = if Path::new(value).is_file() {
+            vec![value.to_string()]
+        } else if cfg!(windows) {
+            value.split_whitespace().map(str::to_string).collect()
+        } else {
+            shlex::split(value)?
+        };
+        if words.is_empty() {
+            return None;
+        }
+        let program = words.remove(0);
+        Some(Self {
+            program,
+            args: words,
+        })
+    }
+
+    pub fn open(&self, locations: &[&Location], layout: Option<Layout>) -> Result<()> {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        if let [location] = locations {
+            command.args(editor_args(&self.program, location));
+        } else {
+            if let Some(layout) = layout {
+                command.args(layout_args(&self.program, layout));
+            }
+            command.args(locations.iter().map(|location| &location.path));
+        }
+
+        command
+            .status()
+            .with_context(|| format!("Failed to open {}", self.program))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(arg: &str) -> (&str, Option<(u32, Option<u32>)>) {
+        let (path, position) = split_position(arg);
+        (path, position.map(|p| (p.line, p.column)))
+    }
+
+    fn args(editor: &str, line: u32, column: Option<u32>) -> Vec<String> {
+        let location = Location::new("src/main.rs", Some(Position { line, column }));
+        editor_args(editor, &location)
+    }
+
+    #[test]
+    fn splits_line_and_column() {
+        assert_eq!(
+            position("src/main.rs:42"),
+            ("src/main.rs", Some((42, None)))
+        );
+        assert_eq!(
+            position("src/main.rs:42:7"),
+            ("src/main.rs", Some((42, Some(7))))
+        );
+        assert_eq!(
+            position("src/main.rs:42:7:"),
+            ("src/main.rs", Some((42, Some(7))))
+        );
+        assert_eq!(position("src/main.rs"), ("src/main.rs", None));
+        assert_eq!(position(r"C:\foo"), (r"C:\foo", None));
+        assert_eq!(position(r"C:\foo:3"), (r"C:\foo", Some((3, None))));
+    }
+
+    #[test]
+    fn strips_position_from_last_keyword() {
+        let keywords = vec!["src".to_string(), "main:42".to_string()];
+        let (keywords, position) = strip_position(&keywords);
+        assert_eq!(keywords, ["src", "main"]);
+        assert_eq!(position.map(|p| p.line), Some(42));
+    }
+
+    #[test]
+    fn keeps_existing_file_names_ending_in_a_number() {
+        let dir = env::temp_dir().join(format!("texoxide-editor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes:12");
+        std::fs::write(&path, "").unwrap();
+
+        let location = Location::parse(path.to_str().unwrap());
+        assert_eq!(location.path, path.to_str().unwrap());
+        assert!(location.position.is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parses_editor_commands() {
+        let editor = Editor::parse("code --wait").unwrap();
+        assert_eq!(editor.program, "code");
+        assert_eq!(editor.args, ["--wait"]);
+
+        let editor = Editor::parse("'my editor' -f").unwrap();
+        assert_eq!(editor.program, "my editor");
+        assert_eq!(editor.args, ["-f"]);
+
+        assert!(Editor::parse("vim 'unclosed").is_none());
+    }
+
+    #[test]
+    fn keeps_unquoted_editor_paths_that_exist() {
+        let dir = env::temp_dir().join(format!("texoxide-editor-path-{}", std::process::id()));
+        let path = dir.join("Sublime Text");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, "").unwrap();
+
+        let editor = Editor::parse(&format!(" {} ", path.display())).unwrap();
+        assert_eq!(editor.program, path.to_str().unwrap());
+        assert!(editor.args.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn editor_argument_shapes() {
+        assert_eq!(args("vim", 42, None), ["+42", "src/main.rs"]);
+        assert_eq!(
+            args("/usr/bin/nvim", 42, Some(7)),
+            ["+call cursor(42, 7)", "src/main.rs"]
+        );
+        assert_eq!(args("code", 42, Some(7)), ["-g", "src/main.rs:42:7"]);
+        assert_eq!(args("hx", 42, Some(7)), ["src/main.rs:42:7"]);
+        assert_eq!(args("emacs", 42, Some(7)), ["+42:7", "src/main.rs"]);
+        assert_eq!(args("nano", 42, Some(7)), ["+42,7", "src/main.rs"]);
+        assert_eq!(args("notepad", 42, Some(7)), ["src/main.rs"]);
+    }
+}
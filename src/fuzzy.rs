@@ -0,0 +1,232 @@
+const SCORE_MATCH: i32 = 16;
+const SCORE_GAP_START: i32 = -5;
+const SCORE_GAP_EXTENSION: i32 = -1;
+
+const BONUS_BOUNDARY: i32 = 8;
+const BONUS_SEPARATOR: i32 = 9;
+const BONUS_CAMEL: i32 = 7;
+const BONUS_CONSECUTIVE: i32 = 8;
+const BONUS_BASENAME: i32 = 4;
+const BONUS_FIRST_CHAR_MULTIPLIER: i32 = 2;
+
+pub struct Match {
+    pub score: i32,
+    pub positions: Vec<usize>,
+}
+
+fn is_separator(c: char) -> bool {
+    c == '/' || c == '\\'
+}
+
+fn chars_eq(a: char, b: char, case_sensitive: bool) -> bool {
+    if a == b {
+        true
+    } else if case_sensitive {
+        false
+    } else if a.is_ascii() && b.is_ascii() {
+        a.eq_ignore_ascii_case(&b)
+    } else {
+        a.to_lowercase().eq(b.to_lowercase())
+    }
+}
+
+fn bonus(prev: Option<char>, c: char) -> i32 {
+    match prev {
+        None => BONUS_BOUNDARY,
+        Some(p) if is_separator(p) => BONUS_SEPARATOR,
+        Some(p) if !p.is_alphanumeric() && c.is_alphanumeric() => BONUS_BOUNDARY,
+        Some(p) if p.is_lowercase() && c.is_uppercase() => BONUS_CAMEL,
+        Some(p) if !p.is_numeric() && c.is_numeric() => BONUS_CAMEL,
+        _ => 0,
+    }
+}
+
+fn is_subsequence(pattern: &[char], text: &[char], case_sensitive: bool) -> bool {
+    let mut text = text.iter();
+    pattern
+        .iter()
+        .all(|&p| text.any(|&c| chars_eq(p, c, case_sensitive)))
+}
+
+fn align(pattern: &[char], text: &[char], bonuses: &[i32], case_sensitive: bool) -> Option<Match> {
+    if !is_subsequence(pattern, text, case_sensitive) {
+        return None;
+    }
+
+    let (m, n) = (pattern.len(), text.len());
+
+    // scores[i][j] is the best score with pattern[..=i] matched and pattern[i] at text[j],
+    // from[i][j] is where pattern[i - 1] sits in that alignment.
+    let mut scores = vec![vec![None::<i32>; n]; m];
+    let mut from = vec![vec![0usize; n]; m];
+
+    for j in 0..n {
+        if chars_eq(pattern[0], text[j], case_sensitive) {
+            scores[0][j] = Some(SCORE_MATCH + bonuses[j] * BONUS_FIRST_CHAR_MULTIPLIER);
+        }
+    }
+
+    for i in 1..m {
+        let mut gap: Option<(i32, usize)> = None;
+        for j in i..n {
+            if j >= 2 {
+                gap = gap.map(|(s, k)| (s + SCORE_GAP_EXTENSION, k));
+                if let Some(s) = scores[i - 1][j - 2] {
+                    let opened = s + SCORE_GAP_START;
+                    if gap.is_none_or(|(g, _)| opened > g) {
+                        gap = Some((opened, j - 2));
+                    }
+                }
+            }
+            if !chars_eq(pattern[i], text[j], case_sensitive) {
+                continue;
+            }
+
+            let consecutive = scores[i - 1][j - 1].map(|s| (s + BONUS_CONSECUTIVE, j - 1));
+            let best = match (consecutive, gap) {
+                (Some(c), Some(g)) => Some(if g.0 > c.0 { g } else { c }),
+                (c, g) => c.or(g),
+            };
+            if let Some((s, k)) = best {
+                scores[i][j] = Some(s + SCORE_MATCH + bonuses[j]);
+                from[i][j] = k;
+            }
+        }
+    }
+
+    let (end, score) = scores[m - 1]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, s)| s.map(|s| (j, s)))
+        .max_by_key(|&(_, s)| s)?;
+
+    let mut positions = vec![0; m];
+    let mut j = end;
+    for i in (0..m).rev() {
+        positions[i] = j;
+        j = from[i][j];
+    }
+
+    Some(Match { score, positions })
+}
+
+/// Scores `candidate` against `keywords`, fzf style. Every keyword has to match
+/// as a subsequence after the keywords before it. With several keywords the
+/// last one has to match inside the final path component, like zoxide, while a
+/// single keyword may match anywhere in the path.
+/// Matching ignores case unless a keyword contains an uppercase letter.
+pub fn match_keywords(keywords: &[String], candidate: &str) -> Option<Match> {
+    let case_sensitive = keywords.iter().any(|k| k.chars().any(char::is_uppercase));
+    let text: Vec<char> = candidate.chars().collect();
+    let basename = text
+        .iter()
+        .rposition(|&c| is_separator(c))
+        .map_or(0, |i| i + 1);
+    let bonuses: Vec<i32> = (0..text.len())
+        .map(|j| {
+            let b = bonus(j.checked_sub(1).map(|k| text[k]), text[j]);
+            if j >= basename {
+                b + BONUS_BASENAME
+            } else {
+                b
+            }
+        })
+        .collect();
+
+    let mut end = text.len();
+    let mut score = 0;
+    let mut positions = Vec::new();
+    let keywords: Vec<&String> = keywords.iter().filter(|k| !k.is_empty()).collect();
+    for (i, keyword) in keywords.iter().rev().enumerate() {
+        let pattern: Vec<char> = keyword.chars().collect();
+        let start = if i == 0 && keywords.len() > 1 {
+            basename
+        } else {
+            0
+        };
+        if start >= end {
+            return None;
+        }
+        let m = align(
+            &pattern,
+            &text[start..end],
+            &bonuses[start..end],
+            case_sensitive,
+        )?;
+        end = start + m.positions[0];
+        score += m.score;
+        positions.extend(m.positions.iter().map(|p| start + p));
+    }
+
+    positions.sort_unstable();
+    Some(Match { score, positions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keywords(words: &[&str]) -> Vec<String> {
+        words.iter().map(ToString::to_string).collect()
+    }
+
+    fn score(words: &[&str], candidate: &str) -> i32 {
+        match_keywords(&keywords(words), candidate)
+            .expect("should match")
+            .score
+    }
+
+    fn matched(words: &[&str], candidate: &str) -> String {
+        let chars: Vec<char> = candidate.chars().collect();
+        match_keywords(&keywords(words), candidate)
+            .expect("should match")
+            .positions
+            .iter()
+            .map(|&p| chars[p])
+            .collect()
+    }
+
+    #[test]
+    fn matches_subsequence() {
+        assert_eq!(
+            matched(&["cfgfish"], "/home/me/.config/fish/config.fish"),
+            "cfgfish"
+        );
+        assert!(match_keywords(&keywords(&["fishcfg"]), "/home/me/.config/fish/x").is_none());
+    }
+
+    #[test]
+    fn prefers_basename_boundary_and_consecutive_matches() {
+        assert!(score(&["conf"], "/a/conf.txt") > score(&["conf"], "/a/xconfx/x.txt"));
+        assert!(score(&["fb"], "/x/foo_bar") > score(&["fb"], "/x/xfxxbx"));
+        assert!(score(&["abc"], "/x/abc") > score(&["abc"], "/x/axbxc"));
+    }
+
+    #[test]
+    fn last_keyword_matches_the_file_name() {
+        assert!(
+            match_keywords(&keywords(&["fish", "conf"]), "/.config/fish/config.fish").is_some()
+        );
+        assert!(match_keywords(&keywords(&["config", "fish"]), "/fish/config.txt").is_none());
+        assert!(match_keywords(&keywords(&["fish"]), "/fish/config.txt").is_some());
+        // Earlier keywords have to come before later ones
+        assert!(match_keywords(&keywords(&["txt", "config"]), "/fish/config.txt").is_none());
+    }
+
+    #[test]
+    fn smart_case_handles_non_ascii() {
+        assert!(match_keywords(&keywords(&["über"]), "/docs/Über.md").is_some());
+        assert!(match_keywords(&keywords(&["ÜBER"]), "/docs/über.md").is_none());
+        assert!(match_keywords(&keywords(&["Über"]), "/docs/Über.md").is_some());
+        assert!(match_keywords(&keywords(&["Readme"]), "/docs/readme.md").is_none());
+    }
+
+    #[test]
+    fn positions_are_char_indices_in_order() {
+        let candidate = "/home/jürgen/ß/Fish.rs";
+        assert_eq!(matched(&["jü", "fish"], candidate), "jüFish");
+        let m = match_keywords(&keywords(&["ßfr"]), candidate).expect("should match");
+        assert!(m.positions.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(matched(&["ßfr"], candidate), "ßFr");
+    }
+}
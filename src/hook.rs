@@ -0,0 +1,90 @@
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Editor {
+    Vim,
+    #[value(alias = "nvim")]
+    Neovim,
+    Emacs,
+    #[value(alias = "hx")]
+    Helix,
+    #[value(alias = "code")]
+    Vscode,
+}
+
+const VIM: &str = r#"" texoxide: add to ~/.vimrc
+function! s:TexoxideAdd(path) abort
+    if &buftype !=# '' || !filereadable(a:path)
+        return
+    endif
+    if exists('*job_start')
+        call job_start(['texoxide', 'add', '--', a:path])
+    else
+        silent! call system('texoxide add -- ' . shellescape(a:path))
+    endif
+endfunction
+
+augroup texoxide
+    autocmd!
+    autocmd BufReadPost,BufWritePost * call s:TexoxideAdd(expand('<afile>:p'))
+augroup END
+"#;
+
+const NEOVIM: &str = r#"-- texoxide: add to ~/.config/nvim/init.lua
+vim.api.nvim_create_autocmd({ "BufReadPost", "BufWritePost" }, {
+  group = vim.api.nvim_create_augroup("texoxide", { clear = true }),
+  callback = function(args)
+    if vim.bo[args.buf].buftype ~= "" then
+      return
+    end
+    local path = vim.api.nvim_buf_get_name(args.buf)
+    if path ~= "" and vim.fn.filereadable(path) == 1 then
+      vim.fn.jobstart({ "texoxide", "add", "--", path }, { detach = true })
+    end
+  end,
+})
+"#;
+
+const EMACS: &str = r#";; texoxide: add to ~/.emacs.d/init.el
+(defun texoxide-add ()
+  "Record the current file in texoxide."
+  (when (and buffer-file-name (executable-find "texoxide"))
+    (call-process "texoxide" nil 0 nil "add" "--" buffer-file-name)))
+
+(add-hook 'find-file-hook #'texoxide-add)
+(add-hook 'after-save-hook #'texoxide-add)
+"#;
+
+const HELIX: &str = r#"# texoxide: add to ~/.config/helix/config.toml
+# Helix has no open or save hooks, so this records the file when saving with Ctrl-S.
+# It needs a Helix release with command expansions (%{buffer_name}).
+# Files opened with hx from the shell are covered by `texoxide init`.
+[keys.normal]
+C-s = [":write", ":run-shell-command texoxide add -- %{buffer_name}"]
+
+[keys.insert]
+C-s = ["normal_mode", ":write", ":run-shell-command texoxide add -- %{buffer_name}"]
+"#;
+
+const VSCODE: &str = r#"// texoxide: add to settings.json
+// Needs the Run on Save extension (emeraldwalk.RunOnSave), files are recorded when saved.
+"emeraldwalk.runonsave": {
+    "commands": [
+        {
+            "match": ".*",
+            "isAsync": true,
+            "cmd": "texoxide add -- \"${file}\""
+        }
+    ]
+}
+"#;
+
+pub fn snippet(editor: Editor) -> &'static str {
+    match editor {
+        Editor::Vim => VIM,
+        Editor::Neovim => NEOVIM,
+        Editor::Emacs => EMACS,
+        Editor::Helix => HELIX,
+        Editor::Vscode => VSCODE,
+    }
+}
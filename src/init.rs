@@ -0,0 +1,119 @@
+use clap::ValueEnum;
+
+const EDITORS: [&str; 5] = ["vim", "nvim", "nano", "hx", "emacs"];
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+// Functions that may share a name with an alias, like `t` or `vim=nvim`, are defined
+// with `function name`, which keeps the alias from being expanded in the definition
+const POSIX: &str = r#"# texoxide
+# Everything after the options is a query, so `__CMD__ init` searches instead of
+# running a subcommand
+function __CMD__ {
+    local -a opts=()
+    while [ $# -gt 0 ]; do
+        case "$1" in
+            --) shift; break ;;
+            --nth) opts+=("$1" "${2-}"); shift; [ $# -gt 0 ] && shift ;;
+            -?*) opts+=("$1"); shift ;;
+            *) break ;;
+        esac
+    done
+    command texoxide "${opts[@]}" -- "$@"
+}
+
+__texoxide_edit() {
+    local editor="$1"
+    shift
+    command "$editor" "$@"
+    local ret=$?
+    local -a files=()
+    local arg
+    for arg in "$@"; do
+        case "$arg" in
+            -* | +*) ;;
+            *) [ -f "$arg" ] && files+=("$arg") ;;
+        esac
+    done
+    if [ "${#files[@]}" -gt 0 ]; then
+        command texoxide add -- "${files[@]}" >/dev/null 2>&1
+    fi
+    return $ret
+}
+"#;
+
+const POSIX_EDITOR: &str = r#"
+if command -v __EDITOR__ >/dev/null 2>&1; then
+    function __EDITOR__ { __texoxide_edit __EDITOR__ "$@"; }
+fi
+"#;
+
+const FISH: &str = r"# texoxide
+# Everything after the options is a query, so `__CMD__ init` searches instead of
+# running a subcommand
+function __CMD__ --wraps texoxide
+    set -l opts
+    while set -q argv[1]
+        switch $argv[1]
+            case --
+                set -e argv[1]
+                break
+            case --nth
+                set -a opts $argv[1] $argv[2]
+                set -e argv[1]
+                set -q argv[1]; and set -e argv[1]
+            case '-?*'
+                set -a opts $argv[1]
+                set -e argv[1]
+            case '*'
+                break
+        end
+    end
+    command texoxide $opts -- $argv
+end
+
+function __texoxide_edit
+    set -l editor $argv[1]
+    set -e argv[1]
+    command $editor $argv
+    set -l ret $status
+    set -l files
+    for arg in $argv
+        if not string match -q -- '-*' $arg; and not string match -q -- '+*' $arg; and test -f $arg
+            set -a files $arg
+        end
+    end
+    if test (count $files) -gt 0
+        command texoxide add -- $files >/dev/null 2>&1
+    end
+    return $ret
+end
+";
+
+const FISH_EDITOR: &str = r"
+if command -q __EDITOR__
+    function __EDITOR__ --wraps __EDITOR__
+        __texoxide_edit __EDITOR__ $argv
+    end
+end
+";
+
+pub fn script(shell: Shell, cmd: &str, wrap_editors: bool) -> String {
+    let (base, editor) = match shell {
+        Shell::Bash | Shell::Zsh => (POSIX, POSIX_EDITOR),
+        Shell::Fish => (FISH, FISH_EDITOR),
+    };
+
+    let mut script = base.replace("__CMD__", cmd);
+    if wrap_editors {
+        for name in EDITORS {
+            script.push_str(&editor.replace("__EDITOR__", name));
+        }
+    }
+    script
+}
@@ -0,0 +1,125 @@
+use std::{
+    collections::VecDeque,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+// How far up the search climbs, and how much of each level and in total it looks through
+const LEVELS: usize = 3;
+const DIRS_PER_LEVEL: usize = 2_000;
+const TIME_LIMIT: Duration = Duration::from_millis(300);
+
+/// Returns the closest ancestor of `path` that still exists.
+pub fn nearest_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors().skip(1).find(|dir| dir.is_dir())
+}
+
+/// Returns the device the filesystem holding `path` is on, where the platform tells.
+#[cfg(unix)]
+pub fn device(path: &Path) -> Option<i64> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path)
+        .ok()
+        .map(|metadata| metadata.dev().cast_signed())
+}
+
+#[cfg(not(unix))]
+pub fn device(_path: &Path) -> Option<i64> {
+    None
+}
+
+/// Looks for a file with the same name as `path`, starting below its nearest existing
+/// ancestor and then below the `LEVELS` directories above that, closest first. Each
+/// level looks at no more than `DIRS_PER_LEVEL` directories, and the whole search
+/// gives up after `TIME_LIMIT`.
+pub fn find_moved(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?;
+    let deadline = Instant::now() + TIME_LIMIT;
+    let mut searched = None;
+    // Searching from the root would look through the whole system
+    for dir in nearest_ancestor(path)?
+        .ancestors()
+        .filter(|dir| dir.parent().is_some())
+        .take(LEVELS + 1)
+    {
+        if let Some(found) = search_below(dir, name, searched, deadline) {
+            return Some(found);
+        }
+        if Instant::now() >= deadline {
+            break;
+        }
+        searched = Some(dir);
+    }
+    None
+}
+
+/// Breadth-first search below `root`, leaving out the already searched `skip`.
+fn search_below(
+    root: &Path,
+    name: &OsStr,
+    skip: Option<&Path>,
+    deadline: Instant,
+) -> Option<PathBuf> {
+    let mut queue = VecDeque::from([root.to_path_buf()]);
+    let mut visited = 0;
+
+    while let Some(dir) = queue.pop_front() {
+        visited += 1;
+        if visited > DIRS_PER_LEVEL || Instant::now() >= deadline {
+            break;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut subdirs = Vec::new();
+        for entry in entries.flatten() {
+            // Symlinked directories are skipped so links can't send us in circles
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                let subdir = entry.path();
+                if Some(subdir.as_path()) != skip {
+                    subdirs.push(subdir);
+                }
+            } else if file_type.is_file() && entry.file_name() == name {
+                return Some(entry.path());
+            }
+        }
+        subdirs.sort();
+        queue.extend(subdirs);
+    }
+    None
+}
+
+/// Creates an empty file at `path`, along with any missing directories.
+pub fn recreate(path: &Path) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_files_moved_to_a_sibling_directory() {
+        let root = std::env::temp_dir().join(format!("texoxide-locate-{}", std::process::id()));
+        let old = root.join("project/notes/todo.md");
+        let new = root.join("project/archive/todo.md");
+        fs::create_dir_all(old.parent().unwrap()).unwrap();
+        fs::create_dir_all(new.parent().unwrap()).unwrap();
+        fs::write(&new, "").unwrap();
+
+        assert_eq!(find_moved(&old), Some(new));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
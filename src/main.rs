@@ -1,32 +1,89 @@
+mod config;
+mod editor;
+mod fuzzy;
+mod hook;
+mod init;
+mod locate;
+mod opener;
+mod preview;
+mod watch;
+
 use anyhow::{Context, Result};
 use camino::{Utf8Path, Utf8PathBuf};
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::{
+    engine::{ArgValueCompleter, CompletionCandidate},
+    env::Shells,
+    CompleteEnv,
+};
+use config::Config;
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind},
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use directories::ProjectDirs;
+use editor::{Location, Position};
+use preview::Previewer;
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Alignment, Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState},
     Frame, Terminal,
 };
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
 use std::{
+    collections::{HashMap, HashSet},
     env,
-    io::{self, stdout},
-    process::Command,
+    ffi::OsStr,
+    fs,
+    io::{self, stdout, Write},
+    path::Path,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+const HOUR: i64 = 60 * 60;
+const DAY: i64 = 24 * HOUR;
+const WEEK: i64 = 7 * DAY;
+const DEFAULT_MAX_AGE: f64 = 10_000.0;
+const FRECENCY_WEIGHT: f64 = 10.0;
+const DEFAULT_QUERY_LIMIT: usize = 20;
+// How far the best match has to beat the next one, about two matched characters
+const JUMP_MARGIN: f64 = 32.0;
+const CHECK_BATCH: usize = 200;
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const MIGRATIONS: [&str; 5] = [
+    "ALTER TABLE files ADD COLUMN opener TEXT",
+    "ALTER TABLE files ADD COLUMN missing_since INTEGER",
+    "ALTER TABLE files ADD COLUMN checked_at INTEGER",
+    "ALTER TABLE files ADD COLUMN device INTEGER",
+    // Scores are the closest thing to a count older databases have
+    "ALTER TABLE files ADD COLUMN opens INTEGER NOT NULL DEFAULT 1;
+     UPDATE files SET opens = MAX(1, CAST(frequency AS INTEGER))",
+];
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    #[arg(value_name = "QUERY")]
-    query: Option<String>,
+    #[arg(value_name = "QUERY", add = ArgValueCompleter::new(complete_query))]
+    query: Vec<String>,
+
+    /// Always pick from the list, even when one match stands out
+    #[arg(short, long, conflicts_with = "jump")]
+    interactive: bool,
+
+    /// Open the best match without showing the list
+    #[arg(short, long)]
+    jump: bool,
+
+    /// Open the Nth best match without showing the list
+    #[arg(long, value_name = "N", conflicts_with_all = ["interactive", "jump"])]
+    nth: Option<usize>,
 
     #[command(subcommand)]
     command: Option<Commands>,
@@ -34,10 +91,88 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Remove a file from the database
     Remove {
         #[arg(value_name = "FILE_PATH")]
         file_path: String,
     },
+    /// Record files in the database without opening them
+    Add {
+        #[arg(value_name = "FILE_PATH", required = true)]
+        file_paths: Vec<String>,
+
+        /// Amount to increase each file's score by
+        #[arg(short, long, default_value_t = 1.0, value_parser = parse_weight)]
+        weight: f64,
+    },
+    /// Follow tracked files when they're moved or renamed (Linux only)
+    Watch,
+    /// Forget missing, unavailable, excluded and aged out files
+    Cleanup {
+        /// Only list what would be removed
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Show or set the command a file is always opened with
+    Opener {
+        #[arg(value_name = "FILE_PATH")]
+        file_path: String,
+
+        #[arg(value_name = "COMMAND", conflicts_with = "clear")]
+        command: Option<String>,
+
+        /// Go back to the opener rules and the editor
+        #[arg(long)]
+        clear: bool,
+    },
+    /// Print a shell snippet that sets up texoxide
+    Init {
+        #[arg(value_enum)]
+        shell: init::Shell,
+
+        /// Name of the short alias for texoxide
+        #[arg(long, value_name = "NAME", default_value = "t")]
+        cmd: String,
+
+        /// Don't wrap editors to record the files they open
+        #[arg(long)]
+        no_editors: bool,
+    },
+    /// Print an editor snippet that records the files you open
+    Hook {
+        #[arg(value_enum)]
+        editor: hook::Editor,
+    },
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: init::Shell,
+    },
+    /// Print matching files without opening the UI
+    Query {
+        #[arg(value_name = "QUERY", add = ArgValueCompleter::new(complete_query))]
+        keywords: Vec<String>,
+
+        /// Print every match instead of the first 20
+        #[arg(short, long, conflicts_with = "first")]
+        list: bool,
+
+        /// Print the score of each match
+        #[arg(short, long)]
+        score: bool,
+
+        /// Print at most N matches
+        #[arg(short = 'n', long, value_name = "N")]
+        limit: Option<usize>,
+
+        /// Print matches as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Print only the best match
+        #[arg(short, long)]
+        first: bool,
+    },
 }
 
 struct TermUI {
@@ -61,8 +196,13 @@ impl TermUI {
         Ok(())
     }
 
-    fn show_search_results(&mut self, items: &[String], title: &str) -> Result<Option<usize>> {
-        let mut menu = Menu::new(items, title);
+    fn show_search_results(
+        &mut self,
+        texoxide: &Texoxide,
+        entries: Vec<Entry>,
+        query: &str,
+    ) -> Result<Vec<Location>> {
+        let mut menu = Menu::new(entries, query);
         loop {
             self.terminal.draw(|f| ui(f, &mut menu))?;
 
@@ -71,11 +211,46 @@ impl TermUI {
                     continue;
                 }
 
+                let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                menu.status = None;
                 match key.code {
                     KeyCode::Up => menu.previous(),
                     KeyCode::Down => menu.next(),
-                    KeyCode::Enter => return Ok(menu.state.selected()),
-                    KeyCode::Esc => return Ok(None),
+                    KeyCode::Char('p') if ctrl => menu.previous(),
+                    KeyCode::Char('n') if ctrl => menu.next(),
+                    KeyCode::Char('u') if ctrl => menu.set_input(String::new()),
+                    KeyCode::Char('w') if ctrl => {
+                        let input = menu.input.trim_end();
+                        let keep = input
+                            .char_indices()
+                            .rfind(|(_, c)| c.is_whitespace())
+                            .map_or(0, |(i, c)| i + c.len_utf8());
+                        menu.set_input(input[..keep].to_string());
+                    }
+                    KeyCode::Char('t') if ctrl => menu.show_preview = !menu.show_preview,
+                    KeyCode::Char('l') if ctrl => menu.show_details = !menu.show_details,
+                    KeyCode::Char('d') if ctrl => menu.remove_missing(texoxide)?,
+                    KeyCode::Char('f') if ctrl => menu.find_missing(texoxide)?,
+                    KeyCode::Char('r') if ctrl => menu.recreate_missing(),
+                    KeyCode::Char('c') if ctrl => return Ok(Vec::new()),
+                    KeyCode::Tab => menu.toggle_mark(),
+                    KeyCode::Char(' ') if !menu.typing => menu.toggle_mark(),
+                    KeyCode::Char(c) if !ctrl => {
+                        let mut input = menu.input.clone();
+                        input.push(c);
+                        menu.set_input(input);
+                    }
+                    KeyCode::Backspace => {
+                        let mut input = menu.input.clone();
+                        input.pop();
+                        menu.set_input(input);
+                    }
+                    KeyCode::Enter => {
+                        if let Some(locations) = menu.chosen() {
+                            return Ok(locations);
+                        }
+                    }
+                    KeyCode::Esc => return Ok(Vec::new()),
                     _ => {}
                 }
             }
@@ -93,6 +268,200 @@ impl Drop for TermUI {
     }
 }
 
+fn highlight_matches<'a>(display: &'a str, positions: &[usize], skipped: usize) -> Line<'a> {
+    let matched = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut in_match = false;
+    for (i, (offset, _)) in display.char_indices().enumerate() {
+        let hit = positions.binary_search(&(i + skipped)).is_ok();
+        if hit != in_match && offset > start {
+            let text = &display[start..offset];
+            spans.push(if in_match {
+                Span::styled(text, matched)
+            } else {
+                Span::raw(text)
+            });
+            start = offset;
+        }
+        in_match = hit;
+    }
+    let rest = &display[start..];
+    spans.push(if in_match {
+        Span::styled(rest, matched)
+    } else {
+        Span::raw(rest)
+    });
+    Line::from(spans)
+}
+
+fn right_aligned<'a>(text: String) -> Cell<'a> {
+    Cell::from(Line::from(text).alignment(Alignment::Right))
+}
+
+fn relative_time(secs: i64) -> String {
+    let secs = secs.max(0);
+    if secs < 60 {
+        "now".to_string()
+    } else if secs < HOUR {
+        format!("{}m ago", secs / 60)
+    } else if secs < DAY {
+        format!("{}h ago", secs / HOUR)
+    } else if secs < WEEK {
+        format!("{}d ago", secs / DAY)
+    } else if secs < 5 * WEEK {
+        format!("{}w ago", secs / WEEK)
+    } else if secs < 365 * DAY {
+        format!("{}mo ago", secs / (30 * DAY))
+    } else {
+        format!("{}y ago", secs / (365 * DAY))
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes}B")
+    } else {
+        format!("{size:.1}{}", UNITS[unit])
+    }
+}
+
+fn results_header(show_details: bool) -> (Row<'static>, Vec<Constraint>) {
+    let mut header = vec!["  Path", "Opened", "Count", "Score"];
+    let mut widths = vec![
+        Constraint::Fill(1),
+        Constraint::Length(8),
+        Constraint::Length(5),
+        Constraint::Length(7),
+    ];
+    if show_details {
+        header.extend(["Size", "Modified"]);
+        widths.extend([Constraint::Length(7), Constraint::Length(8)]);
+    }
+    let header = Row::new(header.into_iter().enumerate().map(|(i, title)| {
+        if i == 0 {
+            Cell::from(title)
+        } else {
+            right_aligned(title.to_string())
+        }
+    }))
+    .style(Style::default().fg(Color::DarkGray));
+    (header, widths)
+}
+
+fn render_results(f: &mut Frame, menu: &mut Menu, area: Rect) {
+    if menu.results.is_empty() {
+        return;
+    }
+
+    let height = usize::from(area.height.saturating_sub(1));
+    let selected = menu.state.selected().unwrap_or(0);
+    let offset = menu.state.offset().min(selected);
+    let offset = if selected >= offset + height {
+        selected + 1 - height
+    } else {
+        offset
+    };
+    let visible = offset..(offset + height).min(menu.results.len());
+    let details: Vec<Option<(u64, i64)>> = if menu.show_details {
+        visible
+            .clone()
+            .map(|i| menu.details(menu.results[i].index))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let missing: Vec<bool> = visible
+        .clone()
+        .map(|i| !menu.exists(menu.results[i].index))
+        .collect();
+
+    let now = now();
+    let rows: Vec<Row> = menu
+        .results
+        .iter()
+        .enumerate()
+        .map(|(i, hit)| {
+            let entry = &menu.entries[hit.index];
+            let display = if cfg!(windows) {
+                clean_path(&entry.path)
+            } else {
+                entry.path.as_str()
+            };
+            let skipped = entry.path[..entry.path.len() - display.len()]
+                .chars()
+                .count();
+            let mut path = highlight_matches(display, &hit.positions, skipped);
+            let marker = if menu.marked.contains(&hit.index) {
+                Span::styled("● ", Style::default().fg(Color::Green))
+            } else {
+                Span::raw("  ")
+            };
+            path.spans.insert(0, marker);
+            let missing = i
+                .checked_sub(visible.start)
+                .and_then(|i| missing.get(i).copied())
+                .unwrap_or(false);
+            if missing {
+                path.spans.insert(
+                    1,
+                    Span::styled(
+                        "missing ",
+                        Style::default()
+                            .fg(Color::Red)
+                            .add_modifier(Modifier::ITALIC),
+                    ),
+                );
+            }
+            let mut cells = vec![
+                Cell::from(path),
+                right_aligned(relative_time(now - entry.last_accessed)),
+                right_aligned(entry.opens.to_string()),
+                right_aligned(format!("{:.1}", hit.score)),
+            ];
+            if menu.show_details {
+                let (size, modified) = i
+                    .checked_sub(visible.start)
+                    .and_then(|i| details.get(i).copied().flatten())
+                    .map_or((String::new(), String::new()), |(size, mtime)| {
+                        (human_size(size), relative_time(now - mtime))
+                    });
+                cells.push(right_aligned(size));
+                cells.push(right_aligned(modified));
+            }
+            let color = if missing {
+                Color::DarkGray
+            } else {
+                Color::White
+            };
+            Row::new(cells).style(Style::default().fg(color))
+        })
+        .collect();
+
+    let (header, widths) = results_header(menu.show_details);
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .row_highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol(">> ");
+
+    f.render_stateful_widget(table, area, &mut menu.state);
+}
+
 fn ui(f: &mut Frame, menu: &mut Menu) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -107,49 +476,78 @@ fn ui(f: &mut Frame, menu: &mut Menu) {
         )
         .split(f.area());
 
-    // Title
-    let title = Paragraph::new(menu.title.clone())
-        .style(Style::default().add_modifier(Modifier::BOLD))
-        .alignment(Alignment::Center)
-        .block(Block::default().borders(Borders::BOTTOM));
-    f.render_widget(title, chunks[0]);
+    let (list_area, preview_area) = if menu.show_preview {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(chunks[1]);
+        (columns[0], Some(columns[1]))
+    } else {
+        (chunks[1], None)
+    };
 
     // List
-    if !menu.items.is_empty() {
-        let items: Vec<ListItem> = menu
-            .items
-            .iter()
-            .map(|i| {
-                let display = if cfg!(windows) {
-                    clean_path(i)
-                } else {
-                    i.as_str()
-                };
-                ListItem::new(display).style(Style::default().fg(Color::White))
-            })
-            .collect();
+    render_results(f, menu, list_area);
 
-        let list_widget = List::new(items)
-            .block(Block::default().borders(Borders::NONE))
-            .highlight_style(
-                Style::default()
-                    .bg(Color::DarkGray)
-                    .add_modifier(Modifier::BOLD),
-            )
-            .highlight_symbol(">> ");
+    // Search
+    let prompt = Line::from(vec![
+        Span::styled("> ", Style::default().fg(Color::Yellow)),
+        Span::styled(
+            menu.input.as_str(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+    ]);
+    let search_block = Block::default()
+        .borders(Borders::BOTTOM)
+        .title(format!(" {}/{} ", menu.results.len(), menu.entries.len()))
+        .title_alignment(Alignment::Right);
+    let input_area = search_block.inner(chunks[0]);
+    f.render_widget(Paragraph::new(prompt).block(search_block), chunks[0]);
+    let cursor_x = input_area.x + 2 + u16::try_from(menu.input.chars().count()).unwrap_or(u16::MAX);
+    f.set_cursor_position((cursor_x.min(input_area.right()), input_area.y));
 
-        f.render_stateful_widget(list_widget, chunks[1], &mut menu.state);
+    // Preview
+    if let Some(area) = preview_area {
+        let lines = menu.preview(area.height.into());
+        let preview = Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::LEFT)
+                .border_style(Style::default().fg(Color::DarkGray)),
+        );
+        f.render_widget(preview, area);
     }
 
     // Controls
-    let instructions = Line::from(vec![
-        Span::styled("↑/↓", Style::default().fg(Color::Yellow)),
-        Span::raw(" Navigate  "),
-        Span::styled("Enter", Style::default().fg(Color::Green)),
-        Span::raw(" Select  "),
-        Span::styled("Esc", Style::default().fg(Color::Red)),
-        Span::raw(" Exit"),
-    ]);
+    let instructions = if let Some(status) = &menu.status {
+        Line::raw(status.clone())
+    } else if menu.selected_missing().is_some() {
+        Line::from(vec![
+            Span::styled("Missing: ", Style::default().fg(Color::Red)),
+            Span::styled("Ctrl-D", Style::default().fg(Color::Magenta)),
+            Span::raw(" Remove  "),
+            Span::styled("Ctrl-F", Style::default().fg(Color::Magenta)),
+            Span::raw(" Find  "),
+            Span::styled("Ctrl-R", Style::default().fg(Color::Magenta)),
+            Span::raw(" Recreate  "),
+            Span::styled("Esc", Style::default().fg(Color::Red)),
+            Span::raw(" Exit"),
+        ])
+    } else {
+        Line::from(vec![
+            Span::styled("↑/↓", Style::default().fg(Color::Yellow)),
+            Span::raw(" Navigate  "),
+            Span::styled("Tab", Style::default().fg(Color::Cyan)),
+            Span::raw(" Mark  "),
+            Span::styled("Ctrl-T", Style::default().fg(Color::Magenta)),
+            Span::raw(" Preview  "),
+            Span::styled("Ctrl-L", Style::default().fg(Color::Magenta)),
+            Span::raw(" Details  "),
+            Span::styled("Enter", Style::default().fg(Color::Green)),
+            Span::raw(" Select  "),
+            Span::styled("Esc", Style::default().fg(Color::Red)),
+            Span::raw(" Exit"),
+        ])
+    };
 
     let footer = Paragraph::new(instructions)
         .style(Style::default().fg(Color::DarkGray))
@@ -158,6 +556,110 @@ fn ui(f: &mut Frame, menu: &mut Menu) {
     f.render_widget(footer, chunks[2]);
 }
 
+#[derive(Serialize)]
+struct Entry {
+    path: String,
+    frequency: f64,
+    opens: i64,
+    last_accessed: i64,
+}
+
+impl Entry {
+    fn frecency(&self, now: i64) -> f64 {
+        let age = now - self.last_accessed;
+        let multiplier = if age < HOUR {
+            4.0
+        } else if age < DAY {
+            2.0
+        } else if age < WEEK {
+            0.5
+        } else {
+            0.25
+        };
+        self.frequency * multiplier
+    }
+}
+
+fn abs_path(file_path: &str) -> String {
+    Utf8Path::new(file_path)
+        .as_std_path()
+        .canonicalize()
+        .ok()
+        .and_then(|p| Utf8PathBuf::from_path_buf(p).ok())
+        .map_or_else(|| file_path.to_string(), |p| p.to_string())
+}
+
+fn max_age() -> f64 {
+    env::var("TEXOXIDE_MAXAGE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v: &f64| *v > 0.0)
+        .unwrap_or(DEFAULT_MAX_AGE)
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX))
+}
+
+enum Presence {
+    Present,
+    Gone,
+    Unavailable,
+}
+
+/// A missing file is gone when the closest directory above it that still exists is on
+/// the device the file was last seen on. Otherwise the filesystem it was on, like an
+/// unplugged drive or a network share, isn't mounted and the file is unavailable.
+/// Device numbers of removable drives can change between mounts, and files never seen
+/// since the device was recorded have nothing to compare with, both count as
+/// unavailable. Where the platform has no device numbers, only files without any
+/// existing directory above them, like those on a missing drive letter, are.
+fn presence(path: &str, device: Option<i64>) -> Presence {
+    let path = Utf8Path::new(path).as_std_path();
+    if path.exists() {
+        return Presence::Present;
+    }
+    let Some(dir) = locate::nearest_ancestor(path) else {
+        return Presence::Unavailable;
+    };
+    match locate::device(dir) {
+        Some(current) if device != Some(current) => Presence::Unavailable,
+        _ => Presence::Gone,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Reason {
+    Missing,
+    Unavailable,
+    Excluded,
+    AgedOut,
+}
+
+impl Reason {
+    fn label(self) -> &'static str {
+        match self {
+            Reason::Missing => "missing",
+            Reason::Unavailable => "unavailable",
+            Reason::Excluded => "excluded",
+            Reason::AgedOut => "aged out",
+        }
+    }
+}
+
+struct Removal {
+    path: String,
+    reason: Reason,
+}
+
+impl Removal {
+    fn new(path: String, reason: Reason) -> Self {
+        Self { path, reason }
+    }
+}
+
 struct Texoxide {
     conn: Connection,
 }
@@ -171,6 +673,12 @@ impl Texoxide {
         let db_path = data_dir.join("texoxide.db");
 
         let conn = Connection::open(db_path).context("Failed to open database")?;
+        Self::from_connection(conn)
+    }
+
+    /// Sets up the schema on an open database, migrating it if it's from an older version.
+    fn from_connection(conn: Connection) -> Result<Self> {
+        conn.busy_timeout(BUSY_TIMEOUT)?;
         conn.execute(
             "CREATE TABLE IF NOT EXISTS files (
                 path TEXT PRIMARY KEY,
@@ -181,14 +689,26 @@ impl Texoxide {
         )
         .context("Failed to create database schema")?;
 
+        let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            conn.execute_batch(&format!(
+                "BEGIN; {migration}; PRAGMA user_version = {}; COMMIT;",
+                i + 1
+            ))
+            .context("Failed to migrate database schema")?;
+        }
+
         Ok(Self { conn })
     }
 
-    fn add(&self, file_path: &str) -> Result<()> {
+    fn add(&self, file_path: &str, weight: f64) -> Result<()> {
         let path = Utf8Path::new(file_path);
         if !path.as_std_path().exists() {
             anyhow::bail!("File {file_path} does not exist");
         }
+        if path.as_std_path().is_dir() {
+            anyhow::bail!("{file_path} is a directory");
+        }
 
         let canonical = path.as_std_path().canonicalize()?;
         let abs_path = Utf8PathBuf::from_path_buf(canonical)
@@ -196,24 +716,41 @@ impl Texoxide {
             .to_string();
 
         self.conn.execute(
-            "INSERT INTO files (path, frequency) VALUES (?, 1)
+            "INSERT INTO files (path, frequency, device) VALUES (?1, ?2, ?3)
              ON CONFLICT(path) DO UPDATE SET
-                 frequency = frequency + 1,
-                 last_accessed = CURRENT_TIMESTAMP",
-            params![&abs_path],
+                 frequency = frequency + ?2,
+                 opens = opens + 1,
+                 last_accessed = CURRENT_TIMESTAMP,
+                 device = ?3",
+            params![&abs_path, weight, locate::device(Path::new(&abs_path))],
+        )?;
+        self.age()
+    }
+
+    fn age(&self) -> Result<()> {
+        let max_age = max_age();
+        let total: f64 =
+            self.conn
+                .query_row("SELECT COALESCE(SUM(frequency), 0) FROM files", [], |row| {
+                    row.get(0)
+                })?;
+        if total <= max_age {
+            return Ok(());
+        }
+
+        let factor = 0.9 * max_age / total;
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "UPDATE files SET frequency = frequency * ?",
+            params![factor],
         )?;
+        tx.execute("DELETE FROM files WHERE frequency < 1", [])?;
+        tx.commit()?;
         Ok(())
     }
 
     fn remove_entry(&self, file_path: &str) -> Result<()> {
-        let path = Utf8Path::new(file_path);
-        let abs_path = path
-            .as_std_path()
-            .canonicalize()
-            .ok()
-            .and_then(|p| Utf8PathBuf::from_path_buf(p).ok())
-            .map_or_else(|| file_path.to_string(), |p| p.to_string());
-
+        let abs_path = abs_path(file_path);
         let count = self
             .conn
             .execute("DELETE FROM files WHERE path = ?", params![abs_path])?;
@@ -223,119 +760,1008 @@ impl Texoxide {
         Ok(())
     }
 
-    fn cleanup(&self) -> Result<()> {
-        let mut stmt = self.conn.prepare("SELECT path FROM files")?;
-        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
-        let mut to_remove = Vec::new();
-        for row in rows {
-            let path: String = row?;
-            if !Utf8Path::new(&path).as_std_path().exists() {
-                to_remove.push(path);
+    fn opener(&self, file_path: &str) -> Result<Option<String>> {
+        let opener = self
+            .conn
+            .query_row(
+                "SELECT opener FROM files WHERE path = ?",
+                params![abs_path(file_path)],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(opener.flatten())
+    }
+
+    fn set_opener(&self, file_path: &str, opener: Option<&str>) -> Result<()> {
+        let count = self.conn.execute(
+            "UPDATE files SET opener = ? WHERE path = ?",
+            params![opener, abs_path(file_path)],
+        )?;
+        if count == 0 {
+            anyhow::bail!("No entry found for {file_path}");
+        }
+        Ok(())
+    }
+
+    /// Forgets files that have been gone for the grace period, excluded files and
+    /// files aging would drop. Files that are merely unavailable, like those on an
+    /// unmounted drive, get a longer grace period. With `due_only`, only the files checked
+    /// longest ago are looked at, at most `CHECK_BATCH` of them and none checked within
+    /// the last `check_hours`. A dry run only reports what would be removed.
+    fn cleanup(&self, config: &Config, dry_run: bool, due_only: bool) -> Result<Vec<Removal>> {
+        let now = now();
+        let grace = i64::from(config.cleanup.grace_days) * DAY;
+        let unavailable_grace = i64::from(config.cleanup.unavailable_days) * DAY;
+        let checked_before = if due_only {
+            now - i64::from(config.cleanup.check_hours) * HOUR
+        } else {
+            i64::MAX
+        };
+        let excludes = config.exclude_patterns();
+        let mut stmt = self.conn.prepare(
+            "SELECT path, frequency, missing_since, checked_at, device FROM files
+                 ORDER BY checked_at IS NOT NULL, checked_at",
+        )?;
+        #[allow(clippy::type_complexity)]
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<(String, f64, Option<i64>, Option<i64>, Option<i64>)>>>(
+            )?;
+
+        // Paths are all checked before writing, so slow disks don't hold the lock
+        let mut marks = Vec::new();
+        let mut checked = Vec::new();
+        let mut removals = Vec::new();
+        let mut kept = Vec::new();
+        for (path, frequency, missing_since, checked_at, device) in rows {
+            if excludes.iter().any(|glob| glob.matches(&path)) {
+                removals.push(Removal::new(path, Reason::Excluded));
+                continue;
+            }
+            let due = checked_at.is_none_or(|checked_at| checked_at < checked_before);
+            if !due || (due_only && checked.len() >= CHECK_BATCH) {
+                kept.push((path, frequency));
+                continue;
+            }
+            let presence = presence(&path, device);
+            // Devices are refreshed while files are there, drives can come back as another one
+            let current = matches!(presence, Presence::Present)
+                .then(|| locate::device(Path::new(&path)))
+                .flatten();
+            checked.push((path.clone(), current));
+            match (presence, missing_since) {
+                (Presence::Gone | Presence::Unavailable, None) => {
+                    marks.push((path.clone(), Some(now)));
+                }
+                (Presence::Gone, Some(since)) if now - since >= grace => {
+                    removals.push(Removal::new(path, Reason::Missing));
+                    continue;
+                }
+                (Presence::Unavailable, Some(since)) if now - since >= unavailable_grace => {
+                    removals.push(Removal::new(path, Reason::Unavailable));
+                    continue;
+                }
+                (Presence::Present, Some(_)) => marks.push((path.clone(), None)),
+                _ => {}
             }
+            kept.push((path, frequency));
         }
-        for path in to_remove {
-            self.conn
-                .execute("DELETE FROM files WHERE path = ?", params![path])?;
+
+        let max_age = max_age();
+        let total: f64 = kept.iter().map(|(_, frequency)| frequency).sum();
+        if total > max_age {
+            let factor = 0.9 * max_age / total;
+            removals.extend(
+                kept.into_iter()
+                    .filter(|(_, frequency)| frequency * factor < 1.0)
+                    .map(|(path, _)| Removal::new(path, Reason::AgedOut)),
+            );
         }
-        Ok(())
+        if dry_run {
+            return Ok(removals);
+        }
+
+        self.write_checks(now, &marks, &checked, &removals)?;
+        Ok(removals)
     }
 
-    fn query(&self, search_term: &str) -> Result<Vec<String>> {
-        let pattern = format!("%{search_term}%");
+    /// Writes what a cleanup found in one short transaction, then ages what's left.
+    fn write_checks(
+        &self,
+        now: i64,
+        marks: &[(String, Option<i64>)],
+        checked: &[(String, Option<i64>)],
+        removals: &[Removal],
+    ) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut mark = tx.prepare("UPDATE files SET missing_since = ? WHERE path = ?")?;
+            for (path, missing_since) in marks {
+                mark.execute(params![missing_since, path])?;
+            }
+        }
+        {
+            let mut check = tx.prepare(
+                "UPDATE files SET checked_at = ?1, device = COALESCE(?2, device) WHERE path = ?3",
+            )?;
+            for (path, device) in checked {
+                check.execute(params![now, device, path])?;
+            }
+        }
+        {
+            let mut delete = tx.prepare("DELETE FROM files WHERE path = ?")?;
+            for removal in removals {
+                // Aging drops these itself once the others are gone
+                if removal.reason != Reason::AgedOut {
+                    delete.execute(params![removal.path])?;
+                }
+            }
+        }
+        tx.commit()?;
+        self.age()
+    }
+
+    fn entries(&self) -> Result<Vec<Entry>> {
         let mut stmt = self.conn.prepare(
-            "SELECT path
-            FROM files
-            WHERE path LIKE ? ESCAPE '\\'
-            ORDER BY frequency DESC, last_accessed DESC
-            LIMIT 20",
+            "SELECT path, frequency, opens, CAST(strftime('%s', last_accessed) AS INTEGER)
+            FROM files",
         )?;
 
-        let mut rows = stmt.query(params![pattern])?;
-        let mut results = Vec::new();
+        let mut rows = stmt.query([])?;
+        let mut entries = Vec::new();
         while let Some(row) = rows.next()? {
-            results.push(row.get(0)?);
+            entries.push(Entry {
+                path: row.get(0)?,
+                frequency: row.get(1)?,
+                opens: row.get(2)?,
+                last_accessed: row.get(3)?,
+            });
         }
-        Ok(results)
+        Ok(entries)
     }
+
+    /// Moves an entry to a new path, merging it into the entry already there.
+    /// Returns whether there was an entry to move.
+    fn relocate(&self, old: &str, new: &str) -> Result<bool> {
+        let tx = self.conn.unchecked_transaction()?;
+        let merged = tx.execute(
+            "UPDATE files SET
+                 frequency = frequency + (SELECT frequency FROM files WHERE path = ?1),
+                 opens = opens + (SELECT opens FROM files WHERE path = ?1),
+                 last_accessed = MAX(last_accessed, (SELECT last_accessed FROM files WHERE path = ?1))
+             WHERE path = ?2 AND EXISTS (SELECT 1 FROM files WHERE path = ?1)",
+            params![old, new],
+        )?;
+        let moved = if merged == 0 {
+            tx.execute(
+                "UPDATE files SET path = ?2, missing_since = NULL WHERE path = ?1",
+                params![old, new],
+            )?
+        } else {
+            tx.execute("DELETE FROM files WHERE path = ?", params![old])?
+        };
+        tx.commit()?;
+        Ok(moved > 0)
+    }
+
+    /// Moves every entry below the directory `old` to `new`. Entries that would
+    /// clash with one already tracked at the new path are left alone.
+    fn relocate_dir(&self, old: &str, new: &str) -> Result<usize> {
+        let count = self.conn.execute(
+            "UPDATE OR IGNORE files SET path = ?2 || substr(path, length(?1) + 1), missing_since = NULL
+             WHERE substr(path, 1, length(?1) + 1) = ?1 || '/'",
+            params![old, new],
+        )?;
+        Ok(count)
+    }
+
+    fn last(&self) -> Result<Option<String>> {
+        let path = self
+            .conn
+            .query_row(
+                "SELECT path FROM files ORDER BY last_accessed DESC, rowid DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(path)
+    }
+}
+
+struct Hit {
+    index: usize,
+    score: f64,
+    positions: Vec<usize>,
+}
+
+fn rank(entries: &[Entry], keywords: &[String]) -> Vec<Hit> {
+    let now = now();
+    let mut results: Vec<Hit> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry)| {
+            let m = fuzzy::match_keywords(keywords, &entry.path)?;
+            let score = f64::from(m.score) + FRECENCY_WEIGHT * entry.frecency(now).ln_1p();
+            Some(Hit {
+                index,
+                score,
+                positions: m.positions,
+            })
+        })
+        .collect();
+
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    results
+}
+
+#[derive(Serialize)]
+struct QueryResult<'a> {
+    #[serde(flatten)]
+    entry: &'a Entry,
+    score: f64,
 }
 
-fn open_file(file_path: &str) -> Result<()> {
-    #[cfg(windows)]
-    let editor = env::var("EDITOR").unwrap_or_else(|_| "notepad".to_string());
-    #[cfg(not(windows))]
-    let editor = env::var("EDITOR").unwrap_or_else(|_| "vim".to_string());
+fn print_query(
+    texoxide: &Texoxide,
+    keywords: &[String],
+    limit: Option<usize>,
+    score: bool,
+    json: bool,
+) -> Result<()> {
+    let entries = texoxide.entries()?;
+    let hits = rank(&entries, keywords);
+    if hits.is_empty() {
+        anyhow::bail!("No matches for '{}'", keywords.join(" "));
+    }
+
+    let results = hits
+        .iter()
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|hit| QueryResult {
+            entry: &entries[hit.index],
+            score: hit.score,
+        });
 
-    Command::new(editor)
-        .arg(file_path)
-        .status()
-        .context("Failed to open $EDITOR")?;
+    let mut out = io::stdout().lock();
+    if json {
+        serde_json::to_writer_pretty(&mut out, &results.collect::<Vec<_>>())?;
+        writeln!(out)?;
+    } else {
+        for result in results {
+            if score {
+                writeln!(out, "{:>7.1} {}", result.score, result.entry.path)?;
+            } else {
+                writeln!(out, "{}", result.entry.path)?;
+            }
+        }
+    }
     Ok(())
 }
 
-struct Menu<'a> {
-    state: ListState,
-    items: &'a [String],
-    title: String,
+fn parse_weight(value: &str) -> Result<f64, String> {
+    let weight = value.parse::<f64>().map_err(|e| e.to_string())?;
+    // NaN and infinity would end up as a NULL frequency
+    if !(weight.is_finite() && weight > 0.0) {
+        return Err("must be a positive number".to_string());
+    }
+    Ok(weight)
 }
 
-impl<'a> Menu<'a> {
-    fn new(items: &'a [String], title: &str) -> Self {
-        let mut state = ListState::default();
-        state.select(Some(0));
-        Self {
-            state,
-            items,
-            title: title.to_string(),
+fn complete_query(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Some(current) = current.to_str() else {
+        return Vec::new();
+    };
+    let Ok(entries) = Texoxide::new().and_then(|t| t.entries()) else {
+        return Vec::new();
+    };
+
+    let mut seen = HashSet::new();
+    let mut candidates = Vec::new();
+    for hit in rank(&entries, &[]) {
+        let path = entries[hit.index].path.as_str();
+        let name = Utf8Path::new(path).file_name().unwrap_or(path);
+        if name.starts_with(current) && seen.insert(name) {
+            candidates.push(CompletionCandidate::new(name).help(Some(path.to_string().into())));
+        }
+        if !current.is_empty() && path.starts_with(current) && seen.insert(path) {
+            candidates.push(CompletionCandidate::new(path));
         }
     }
 
-    fn next(&mut self) {
-        let i = self
+    candidates
+        .into_iter()
+        .enumerate()
+        .map(|(i, candidate)| candidate.display_order(Some(i)))
+        .collect()
+}
+
+struct Menu {
+    state: TableState,
+    entries: Vec<Entry>,
+    results: Vec<Hit>,
+    input: String,
+    typing: bool,
+    position: Option<Position>,
+    marked: Vec<usize>,
+    show_preview: bool,
+    show_details: bool,
+    details: HashMap<usize, Option<(u64, i64)>>,
+    exists: HashMap<usize, bool>,
+    removed: HashSet<usize>,
+    status: Option<String>,
+    previewer: Option<Previewer>,
+    preview: Option<(String, usize, Vec<Line<'static>>)>,
+}
+
+impl Menu {
+    fn new(entries: Vec<Entry>, input: &str) -> Self {
+        let mut menu = Self {
+            state: TableState::default(),
+            entries,
+            results: Vec::new(),
+            input: String::new(),
+            typing: true,
+            position: None,
+            marked: Vec::new(),
+            show_preview: true,
+            show_details: false,
+            details: HashMap::new(),
+            exists: HashMap::new(),
+            removed: HashSet::new(),
+            status: None,
+            previewer: None,
+            preview: None,
+        };
+        menu.set_input(input.to_string());
+        menu
+    }
+
+    fn set_input(&mut self, input: String) {
+        let keywords: Vec<String> = input.split_whitespace().map(String::from).collect();
+        let (keywords, position) = editor::strip_position(&keywords);
+        self.results = rank(&self.entries, &keywords);
+        self.results
+            .retain(|hit| !self.removed.contains(&hit.index));
+        self.position = position;
+        self.typing = true;
+        self.input = input;
+        self.state.select(if self.results.is_empty() {
+            None
+        } else {
+            Some(0)
+        });
+    }
+
+    fn selected(&self) -> Option<&Entry> {
+        let hit = self.results.get(self.state.selected()?)?;
+        Some(&self.entries[hit.index])
+    }
+
+    fn toggle_mark(&mut self) {
+        let Some(index) = self
             .state
             .selected()
-            .map_or(0, |i| if i >= self.items.len() - 1 { 0 } else { i + 1 });
+            .and_then(|i| self.results.get(i))
+            .map(|hit| hit.index)
+        else {
+            return;
+        };
+        if let Some(i) = self.marked.iter().position(|&m| m == index) {
+            self.marked.remove(i);
+        } else {
+            self.marked.push(index);
+        }
+        self.next();
+    }
+
+    /// The files to open, leaving out missing ones. `None` when all of them are missing.
+    fn chosen(&mut self) -> Option<Vec<Location>> {
+        let indices = if self.marked.is_empty() {
+            self.state
+                .selected()
+                .and_then(|i| self.results.get(i))
+                .map(|hit| hit.index)
+                .into_iter()
+                .collect()
+        } else {
+            self.marked.clone()
+        };
+        let position = if indices.len() == 1 {
+            self.position
+        } else {
+            None
+        };
+
+        let existing: Vec<usize> = indices
+            .iter()
+            .copied()
+            .filter(|&i| self.exists(i))
+            .collect();
+        if existing.is_empty() && !indices.is_empty() {
+            self.status = Some("Missing files can't be opened, Ctrl-R recreates one".to_string());
+            return None;
+        }
+        Some(
+            existing
+                .iter()
+                .map(|&i| Location::new(&self.entries[i].path, position))
+                .collect(),
+        )
+    }
+
+    fn exists(&mut self, index: usize) -> bool {
+        let path = &self.entries[index].path;
+        *self
+            .exists
+            .entry(index)
+            .or_insert_with(|| Path::new(path).exists())
+    }
+
+    /// The entry under the cursor, if its file is missing.
+    fn selected_missing(&mut self) -> Option<usize> {
+        let index = self.results.get(self.state.selected()?)?.index;
+        if self.exists(index) {
+            return None;
+        }
+        Some(index)
+    }
+
+    /// Re-ranks the results, keeping the cursor on the same entry.
+    fn refresh(&mut self, index: usize) {
+        let typing = self.typing;
+        self.set_input(self.input.clone());
+        self.typing = typing;
+        if let Some(i) = self.results.iter().position(|hit| hit.index == index) {
+            self.state.select(Some(i));
+        }
+    }
+
+    fn remove_missing(&mut self, texoxide: &Texoxide) -> Result<()> {
+        let Some(index) = self.selected_missing() else {
+            return Ok(());
+        };
+        let path = self.entries[index].path.clone();
+        texoxide.remove_entry(&path)?;
+        self.removed.insert(index);
+        self.marked.retain(|&m| m != index);
+        let selected = self.state.selected();
+        self.refresh(index);
+        self.state
+            .select(selected.map(|i| i.min(self.results.len().saturating_sub(1))));
+        if self.results.is_empty() {
+            self.state.select(None);
+        }
+        self.status = Some(format!("Removed {path}"));
+        Ok(())
+    }
+
+    fn find_missing(&mut self, texoxide: &Texoxide) -> Result<()> {
+        let Some(index) = self.selected_missing() else {
+            return Ok(());
+        };
+        let path = self.entries[index].path.clone();
+        let Some(found) =
+            locate::find_moved(Path::new(&path)).and_then(|found| found.to_str().map(String::from))
+        else {
+            self.status = Some(format!("No other file named like {path} found"));
+            return Ok(());
+        };
+
+        texoxide.relocate(&path, &found)?;
+        if self.entries.iter().any(|e| e.path == found) {
+            // Already tracked, the entries were merged
+            self.removed.insert(index);
+        } else {
+            self.entries[index].path.clone_from(&found);
+            self.exists.insert(index, true);
+            self.details.remove(&index);
+        }
+        self.refresh(index);
+        self.status = Some(format!("Found at {found}"));
+        Ok(())
+    }
+
+    fn recreate_missing(&mut self) {
+        let Some(index) = self.selected_missing() else {
+            return;
+        };
+        let path = &self.entries[index].path;
+        self.status = Some(match locate::recreate(Path::new(path)) {
+            Ok(()) => {
+                self.exists.insert(index, true);
+                self.details.remove(&index);
+                self.preview = None;
+                format!("Created {path}")
+            }
+            Err(e) => format!("Could not create {path}: {e}"),
+        });
+    }
+
+    fn details(&mut self, index: usize) -> Option<(u64, i64)> {
+        let path = &self.entries[index].path;
+        *self.details.entry(index).or_insert_with(|| {
+            let metadata = fs::metadata(path).ok()?;
+            let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+            Some((metadata.len(), i64::try_from(modified.as_secs()).ok()?))
+        })
+    }
+
+    fn preview(&mut self, height: usize) -> Vec<Line<'static>> {
+        let Some(path) = self.selected().map(|e| e.path.clone()) else {
+            return Vec::new();
+        };
+        if self
+            .preview
+            .as_ref()
+            .is_none_or(|(p, h, _)| *p != path || *h != height)
+        {
+            let previewer = self.previewer.get_or_insert_with(Previewer::new);
+            let lines = previewer.preview(&path, height);
+            self.preview = Some((path, height, lines));
+        }
+        self.preview
+            .as_ref()
+            .map(|(_, _, lines)| lines.clone())
+            .unwrap_or_default()
+    }
+
+    fn next(&mut self) {
+        if self.results.is_empty() {
+            return;
+        }
+        self.typing = false;
+        let i = self.state.selected().map_or(0, |i| {
+            if i >= self.results.len() - 1 {
+                0
+            } else {
+                i + 1
+            }
+        });
         self.state.select(Some(i));
     }
 
     fn previous(&mut self) {
-        let i = self
-            .state
-            .selected()
-            .map_or(0, |i| if i == 0 { self.items.len() - 1 } else { i - 1 });
+        if self.results.is_empty() {
+            return;
+        }
+        self.typing = false;
+        let i = self.state.selected().map_or(0, |i| {
+            if i == 0 {
+                self.results.len() - 1
+            } else {
+                i - 1
+            }
+        });
         self.state.select(Some(i));
     }
 }
 
+/// Drops missing files from the first `count` hits, so only those are looked up.
+fn retain_existing(hits: &mut Vec<Hit>, count: usize, mut exists: impl FnMut(usize) -> bool) {
+    let mut i = 0;
+    while i < count.min(hits.len()) {
+        if exists(hits[i].index) {
+            i += 1;
+        } else {
+            hits.remove(i);
+        }
+    }
+}
+
+fn is_unambiguous(hits: &[Hit]) -> bool {
+    match hits {
+        [_] => true,
+        [best, runner_up, ..] => best.score - runner_up.score >= JUMP_MARGIN,
+        [] => false,
+    }
+}
+
+fn open(texoxide: &Texoxide, config: &Config, locations: &[Location]) -> Result<()> {
+    for location in locations {
+        // A missing file can still be opened, the editor creates it
+        if Path::new(&location.path).is_file() && !config.is_excluded(&abs_path(&location.path)) {
+            texoxide.add(&location.path, 1.0)?;
+        }
+    }
+    opener::open(locations, |l| texoxide.opener(&l.path), config)
+}
+
+fn print_cleanup(removals: &[Removal], dry_run: bool) {
+    for removal in removals {
+        println!("{:<9} {}", removal.reason.label(), removal.path);
+    }
+    if removals.is_empty() {
+        println!("Nothing to clean up");
+        return;
+    }
+
+    let count = |reason| removals.iter().filter(|r| r.reason == reason).count();
+    println!(
+        "{} {} entries ({} missing, {} unavailable, {} excluded, {} aged out)",
+        if dry_run { "Would remove" } else { "Removed" },
+        removals.len(),
+        count(Reason::Missing),
+        count(Reason::Unavailable),
+        count(Reason::Excluded),
+        count(Reason::AgedOut)
+    );
+}
+
+fn run_command(texoxide: &Texoxide, config: &Config, command: Commands) -> Result<()> {
+    match command {
+        Commands::Remove { file_path } => {
+            texoxide.remove_entry(&file_path)?;
+            println!("Removed {file_path} from list");
+        }
+        Commands::Add { file_paths, weight } => {
+            let mut failed = 0;
+            for file_path in &file_paths {
+                if config.is_excluded(&abs_path(file_path)) {
+                    continue;
+                }
+                if let Err(e) = texoxide.add(file_path, weight) {
+                    eprintln!("{e}");
+                    failed += 1;
+                }
+            }
+            if failed > 0 {
+                anyhow::bail!("Failed to add {failed} of {} files", file_paths.len());
+            }
+        }
+        Commands::Watch => watch::watch(texoxide)?,
+        Commands::Cleanup { dry_run } => {
+            let removals = texoxide.cleanup(config, dry_run, false)?;
+            print_cleanup(&removals, dry_run);
+        }
+        Commands::Opener {
+            file_path,
+            command,
+            clear,
+        } => {
+            if clear {
+                texoxide.set_opener(&file_path, None)?;
+            } else if let Some(command) = command {
+                texoxide.set_opener(&file_path, Some(&command))?;
+            } else if let Some(opener) = texoxide.opener(&file_path)? {
+                println!("{opener}");
+            }
+        }
+        Commands::Init {
+            shell,
+            cmd,
+            no_editors,
+        } => {
+            print!("{}", init::script(shell, &cmd, !no_editors));
+        }
+        Commands::Hook { editor } => {
+            print!("{}", hook::snippet(editor));
+        }
+        Commands::Completions { shell } => {
+            let name = shell
+                .to_possible_value()
+                .context("Unknown shell")?
+                .get_name()
+                .to_string();
+            let shells = Shells::builtins();
+            let completer = shells
+                .completer(&name)
+                .with_context(|| format!("No completions for {name}"))?;
+            completer.write_registration(
+                "COMPLETE",
+                "texoxide",
+                "texoxide",
+                "texoxide",
+                &mut io::stdout(),
+            )?;
+        }
+        Commands::Query {
+            keywords,
+            list,
+            score,
+            limit,
+            json,
+            first,
+        } => {
+            let limit = if first {
+                Some(1)
+            } else if list {
+                limit
+            } else {
+                Some(limit.unwrap_or(DEFAULT_QUERY_LIMIT))
+            };
+            print_query(texoxide, &keywords, limit, score, json)?;
+        }
+    }
+    Ok(())
+}
+
 fn main() -> Result<()> {
+    CompleteEnv::with_factory(Cli::command).complete();
+
     let cli = Cli::parse();
     let texoxide = Texoxide::new()?;
 
-    if let Some(Commands::Remove { file_path }) = cli.command {
-        texoxide.remove_entry(&file_path)?;
-        println!("Removed {file_path} from list");
-        return Ok(());
+    let config = Config::load()?;
+
+    if let Some(command) = cli.command {
+        return run_command(&texoxide, &config, command);
+    }
+
+    let cleanup = if !config.cleanup.auto {
+        None
+    } else if config.cleanup.background {
+        let config = config.clone();
+        Some(thread::spawn(move || {
+            Texoxide::new()?.cleanup(&config, false, true).map(drop)
+        }))
+    } else {
+        texoxide.cleanup(&config, false, true)?;
+        None
+    };
+
+    let result = search(&texoxide, &config, &cli);
+    if let Some(cleanup) = cleanup {
+        cleanup
+            .join()
+            .map_err(|_| anyhow::anyhow!("Cleanup thread panicked"))??;
     }
+    result
+}
 
-    let mut ui = TermUI::new()?;
-    texoxide.cleanup()?;
+fn search(texoxide: &Texoxide, config: &Config, cli: &Cli) -> Result<()> {
+    if cli.query == ["-"] {
+        let path = texoxide.last()?.context("No file has been opened yet")?;
+        return open(texoxide, config, &[Location::new(&path, None)]);
+    }
 
-    let search_term = cli.query.as_deref().unwrap_or("");
-    let results = texoxide.query(search_term)?;
+    let search_term = cli.query.join(" ");
+    let direct = Location::parse(&search_term);
+    // An existing file wins over anything in the database, like a directory does for z
+    if !search_term.is_empty() && Path::new(&direct.path).is_file() {
+        return open(texoxide, config, &[direct]);
+    }
 
-    if !results.is_empty() {
-        let selection =
-            ui.show_search_results(&results, &format!(" Matches for '{search_term}' "))?;
-        if let Some(idx) = selection {
-            let path = &results[idx];
-            texoxide.add(path)?;
-            open_file(path)?;
+    let entries = texoxide.entries()?;
+    let (mut keywords, position) = editor::strip_position(&cli.query);
+    let mut hits = rank(&entries, &keywords);
+
+    let mut nth = cli.nth;
+    if nth.is_none() && keywords.len() > 1 {
+        // A trailing number picks a result, unless it only makes sense as a keyword
+        let index = keywords.last().and_then(|k| k.parse::<usize>().ok());
+        let rest = &keywords[..keywords.len() - 1];
+        if let Some(n) = index.filter(|&n| n > 0) {
+            let rest_hits = rank(&entries, rest);
+            if n <= rest_hits.len() {
+                keywords.pop();
+                hits = rest_hits;
+                nth = Some(n);
+            }
         }
-    } else if !search_term.is_empty() && Utf8Path::new(search_term).as_std_path().exists() {
-        texoxide.add(search_term)?;
-        open_file(search_term)?;
+    }
+
+    // Only the hits that can be opened directly need to exist, the list shows missing ones
+    let any_hits = !hits.is_empty();
+    retain_existing(&mut hits, nth.unwrap_or(2), |i| {
+        Path::new(&entries[i].path).exists()
+    });
+
+    if let Some(n) = nth {
+        let hit = n
+            .checked_sub(1)
+            .and_then(|i| hits.get(i))
+            .with_context(|| format!("No match #{n} for '{}'", keywords.join(" ")))?;
+        let location = Location::new(&entries[hit.index].path, position);
+        open(texoxide, config, &[location])?;
+    } else if let Some(best) = hits
+        .first()
+        .filter(|_| cli.jump || (!cli.interactive && !keywords.is_empty() && is_unambiguous(&hits)))
+    {
+        let location = Location::new(&entries[best.index].path, position);
+        open(texoxide, config, &[location])?;
+    } else if any_hits {
+        let locations = TermUI::new()?.show_search_results(texoxide, entries, &search_term)?;
+        open(texoxide, config, &locations)?;
     } else {
         eprintln!("No matches for '{search_term}'");
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hits(scores: &[f64]) -> Vec<Hit> {
+        scores
+            .iter()
+            .enumerate()
+            .map(|(index, &score)| Hit {
+                index,
+                score,
+                positions: Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unambiguous_needs_a_clear_margin() {
+        assert!(!is_unambiguous(&hits(&[])));
+        assert!(is_unambiguous(&hits(&[-20.0])));
+        assert!(is_unambiguous(&hits(&[120.0, 60.0])));
+        assert!(!is_unambiguous(&hits(&[120.0, 100.0])));
+    }
+
+    #[test]
+    fn unambiguous_with_negative_scores() {
+        assert!(!is_unambiguous(&hits(&[-8.0, -12.0])));
+        assert!(is_unambiguous(&hits(&[10.0, -30.0])));
+    }
+
+    fn memory_db(paths: &[(&str, f64)]) -> Texoxide {
+        let texoxide = Texoxide::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        for (path, frequency) in paths {
+            texoxide
+                .conn
+                .execute(
+                    "INSERT INTO files (path, frequency) VALUES (?, ?)",
+                    params![path, frequency],
+                )
+                .unwrap();
+        }
+        texoxide
+    }
+
+    fn tracked(texoxide: &Texoxide) -> Vec<(String, f64)> {
+        let mut entries: Vec<_> = texoxide
+            .entries()
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.path, entry.frequency))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    #[test]
+    fn relocate_dir_only_moves_entries_below_the_directory() {
+        let texoxide = memory_db(&[
+            ("/a/b/x", 1.0),
+            ("/a/b/sub/y", 1.0),
+            ("/a/bc/z", 1.0),
+            ("/a/b/clash", 1.0),
+            ("/n/clash", 2.0),
+        ]);
+        assert_eq!(texoxide.relocate_dir("/a/b", "/n").unwrap(), 2);
+        assert_eq!(
+            tracked(&texoxide),
+            [
+                ("/a/b/clash".to_string(), 1.0),
+                ("/a/bc/z".to_string(), 1.0),
+                ("/n/clash".to_string(), 2.0),
+                ("/n/sub/y".to_string(), 1.0),
+                ("/n/x".to_string(), 1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn relocate_merges_into_a_tracked_entry() {
+        let texoxide = memory_db(&[("/old", 3.0), ("/new", 2.0), ("/other", 1.0)]);
+        assert!(texoxide.relocate("/old", "/new").unwrap());
+        assert!(!texoxide.relocate("/old", "/new").unwrap());
+        assert!(texoxide.relocate("/other", "/moved").unwrap());
+        assert_eq!(
+            tracked(&texoxide),
+            [("/moved".to_string(), 1.0), ("/new".to_string(), 5.0)]
+        );
+        let opens: i64 = texoxide
+            .conn
+            .query_row("SELECT opens FROM files WHERE path = '/new'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(opens, 2);
+    }
+
+    #[test]
+    fn cleanup_forgets_files_gone_past_the_grace_period() {
+        let dir = env::temp_dir().join(format!("texoxide-cleanup-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let kept = dir.join("kept.txt");
+        fs::write(&kept, "").unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+        let texoxide = memory_db(&[
+            (&path("kept.txt"), 1.0),
+            (&path("new.txt"), 1.0),
+            (&path("recent.txt"), 1.0),
+            (&path("old.txt"), 1.0),
+        ]);
+        let device = locate::device(&dir);
+        let mark = |name: &str, days: i64| {
+            texoxide
+                .conn
+                .execute(
+                    "UPDATE files SET missing_since = ?, device = ? WHERE path = ?",
+                    params![now() - days * DAY, device, path(name)],
+                )
+                .unwrap();
+        };
+        mark("kept.txt", 40);
+        mark("recent.txt", 29);
+        mark("old.txt", 31);
+
+        let config = Config::default();
+        let dry_run = texoxide.cleanup(&config, true, false).unwrap();
+        assert_eq!(dry_run.len(), 1);
+        assert_eq!(tracked(&texoxide).len(), 4);
+
+        let removals = texoxide.cleanup(&config, false, false).unwrap();
+        let removed: Vec<_> = removals
+            .iter()
+            .map(|r| (r.path.clone(), r.reason))
+            .collect();
+        assert_eq!(removed, [(path("old.txt"), Reason::Missing)]);
+        let missing_since = |name: &str| -> Option<i64> {
+            texoxide
+                .conn
+                .query_row(
+                    "SELECT missing_since FROM files WHERE path = ?",
+                    params![path(name)],
+                    |row| row.get(0),
+                )
+                .unwrap()
+        };
+        assert!(missing_since("kept.txt").is_none());
+        assert!(missing_since("new.txt").is_some());
+        assert!(missing_since("recent.txt").is_some());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cleanup_keeps_files_on_another_device_longer() {
+        let dir = env::temp_dir().join(format!("texoxide-unavailable-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        let unplugged = locate::device(&dir).map(|device| device + 1);
+
+        let texoxide = memory_db(&[(&path("month.txt"), 1.0), (&path("year.txt"), 1.0)]);
+        for (name, days) in [("month.txt", 31), ("year.txt", 181)] {
+            texoxide
+                .conn
+                .execute(
+                    "UPDATE files SET missing_since = ?, device = ? WHERE path = ?",
+                    params![now() - days * DAY, unplugged, path(name)],
+                )
+                .unwrap();
+        }
+
+        let removals = texoxide.cleanup(&Config::default(), false, false).unwrap();
+        let removed: Vec<_> = removals
+            .iter()
+            .map(|r| (r.path.clone(), r.reason))
+            .collect();
+        assert_eq!(removed, [(path("year.txt"), Reason::Unavailable)]);
+        assert_eq!(tracked(&texoxide), [(path("month.txt"), 1.0)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn weight_must_be_finite_and_positive() {
+        assert_eq!(parse_weight("2.5"), Ok(2.5));
+        for weight in ["0", "-1", "nan", "NaN", "inf", "-inf", "infinity", "x"] {
+            assert!(parse_weight(weight).is_err(), "{weight} was accepted");
+        }
+    }
+}
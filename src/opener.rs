@@ -0,0 +1,146 @@
+use crate::{
+    config::{Config, OpenerRule},
+    editor::{Editor, Location},
+};
+use anyhow::{Context, Result};
+use glob::Pattern;
+use std::{fs::File, io::Read, path::Path, process::Command};
+
+const SNIFF_BYTES: u64 = 8 * 1024;
+
+struct Sniffed {
+    mime: &'static str,
+    binary: bool,
+}
+
+fn sniff(path: &str) -> Sniffed {
+    let mut bytes = Vec::new();
+    if File::open(path)
+        .and_then(|f| f.take(SNIFF_BYTES).read_to_end(&mut bytes))
+        .is_err()
+    {
+        return Sniffed {
+            mime: "application/octet-stream",
+            binary: false,
+        };
+    }
+
+    match infer::get(&bytes) {
+        Some(kind) => Sniffed {
+            mime: kind.mime_type(),
+            binary: kind.matcher_type() != infer::MatcherType::Text,
+        },
+        None if bytes.contains(&0) => Sniffed {
+            mime: "application/octet-stream",
+            binary: true,
+        },
+        None => Sniffed {
+            mime: "text/plain",
+            binary: false,
+        },
+    }
+}
+
+fn mime_matches(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(kind) => mime.split('/').next() == Some(kind),
+        None => pattern == mime,
+    }
+}
+
+impl OpenerRule {
+    fn matches(&self, path: &str, mime: &str) -> bool {
+        let extension = Path::new(path).extension().and_then(|e| e.to_str());
+        self.glob
+            .as_deref()
+            .and_then(|glob| Pattern::new(glob).ok())
+            .is_some_and(|glob| glob.matches(path))
+            || extension.is_some_and(|ext| {
+                self.extensions
+                    .iter()
+                    .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(ext))
+            })
+            || self
+                .mime
+                .as_deref()
+                .is_some_and(|pattern| mime_matches(pattern, mime))
+    }
+}
+
+fn system_opener() -> Vec<String> {
+    let words: &[&str] = if cfg!(windows) {
+        &["cmd", "/C", "start", ""]
+    } else if cfg!(target_os = "macos") {
+        &["open"]
+    } else {
+        &["xdg-open"]
+    };
+    words.iter().map(ToString::to_string).collect()
+}
+
+fn run(mut words: Vec<String>, path: &str) -> Result<()> {
+    if words.iter().any(|w| w.contains("{}")) {
+        for word in &mut words {
+            *word = word.replace("{}", path);
+        }
+    } else {
+        words.push(path.to_string());
+    }
+
+    let program = words.remove(0);
+    Command::new(&program)
+        .args(words)
+        .status()
+        .with_context(|| format!("Failed to open {program}"))?;
+    Ok(())
+}
+
+fn parse(command: &str) -> Result<Vec<String>> {
+    shlex::split(command)
+        .filter(|words| !words.is_empty())
+        .with_context(|| format!("Could not parse opener command: {command}"))
+}
+
+fn opener_for(
+    location: &Location,
+    preferred: Option<String>,
+    rules: &[OpenerRule],
+) -> Result<Option<Vec<String>>> {
+    if let Some(command) = preferred {
+        return parse(&command).map(Some);
+    }
+
+    let sniffed = sniff(&location.path);
+    if let Some(rule) = rules
+        .iter()
+        .find(|rule| rule.matches(&location.path, sniffed.mime))
+    {
+        return parse(&rule.command).map(Some);
+    }
+    if sniffed.binary {
+        return Ok(Some(system_opener()));
+    }
+    Ok(None)
+}
+
+/// Opens each location with, in order: its preferred opener, the first matching
+/// opener rule or the system opener for binary files. Whatever is left is opened
+/// in a single editor invocation.
+pub fn open(
+    locations: &[Location],
+    preferred: impl Fn(&Location) -> Result<Option<String>>,
+    config: &Config,
+) -> Result<()> {
+    let mut in_editor = Vec::new();
+    for location in locations {
+        match opener_for(location, preferred(location)?, &config.openers)? {
+            Some(words) => run(words, &location.path)?,
+            None => in_editor.push(location),
+        }
+    }
+
+    if !in_editor.is_empty() {
+        Editor::from_env()?.open(&in_editor, config.editor.layout)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+use std::{fs::File, io::Read, path::Path};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{self, Theme, ThemeSet},
+    parsing::SyntaxSet,
+};
+
+const MAX_BYTES: u64 = 64 * 1024;
+const THEME: &str = "base16-ocean.dark";
+
+pub struct Previewer {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+fn message(text: String) -> Vec<Line<'static>> {
+    vec![Line::styled(
+        text,
+        Style::default()
+            .fg(Color::DarkGray)
+            .add_modifier(Modifier::ITALIC),
+    )]
+}
+
+fn to_color(color: highlighting::Color) -> Color {
+    Color::Rgb(color.r, color.g, color.b)
+}
+
+impl Previewer {
+    pub fn new() -> Self {
+        let mut themes = ThemeSet::load_defaults().themes;
+        Self {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: themes.remove(THEME).unwrap_or_default(),
+        }
+    }
+
+    pub fn preview(&self, path: &str, max_lines: usize) -> Vec<Line<'static>> {
+        let mut bytes = Vec::new();
+        let read = File::open(path).and_then(|f| f.take(MAX_BYTES).read_to_end(&mut bytes));
+        if let Err(e) = read {
+            return message(format!("Cannot read file: {e}"));
+        }
+        if bytes.contains(&0) {
+            return message("Binary file".to_string());
+        }
+
+        let text = match std::str::from_utf8(&bytes) {
+            Ok(text) => text,
+            // The read may have cut a multi-byte character in half
+            Err(e) if e.error_len().is_none() => {
+                std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default()
+            }
+            Err(_) => return message("Binary file".to_string()),
+        };
+        if text.is_empty() {
+            return message("Empty file".to_string());
+        }
+
+        let syntax = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.syntaxes.find_syntax_by_extension(ext))
+            .or_else(|| {
+                text.lines()
+                    .next()
+                    .and_then(|first| self.syntaxes.find_syntax_by_first_line(first))
+            })
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text());
+
+        let mut highlighter = HighlightLines::new(syntax, &self.theme);
+        text.split_inclusive('\n')
+            .take(max_lines)
+            .map(|line| {
+                let Ok(ranges) = highlighter.highlight_line(line, &self.syntaxes) else {
+                    return Line::raw(line.trim_end().replace('\t', "    "));
+                };
+                let spans: Vec<Span<'static>> = ranges
+                    .into_iter()
+                    .map(|(style, piece)| {
+                        Span::styled(
+                            piece.trim_end_matches(['\n', '\r']).replace('\t', "    "),
+                            Style::default().fg(to_color(style.foreground)),
+                        )
+                    })
+                    .collect();
+                Line::from(spans)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,239 @@
+use crate::Texoxide;
+use anyhow::Result;
+
+#[cfg(target_os = "linux")]
+pub use linux::watch;
+
+#[cfg(not(target_os = "linux"))]
+pub fn watch(_texoxide: &Texoxide) -> Result<()> {
+    anyhow::bail!("texoxide watch uses inotify, which is only available on Linux")
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{Result, Texoxide};
+    use anyhow::Context;
+    use inotify::{EventMask, EventOwned, Inotify, WatchDescriptor, WatchMask, Watches};
+    use std::{
+        collections::{HashMap, VecDeque},
+        io::{self, ErrorKind},
+        path::{Path, PathBuf},
+        sync::mpsc::{self, RecvTimeoutError, Sender},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    const SETTLE: Duration = Duration::from_secs(1);
+    const RESCAN_INTERVAL: Duration = Duration::from_mins(1);
+    const MAX_PENDING: usize = 1024;
+
+    struct Watcher {
+        watches: Watches,
+        dirs: HashMap<WatchDescriptor, PathBuf>,
+        watched: HashMap<PathBuf, WatchDescriptor>,
+        // A move shows up as a MOVED_FROM and a MOVED_TO event sharing a cookie
+        pending: HashMap<u32, PathBuf>,
+        // File moves wait a moment, editors that save by renaming write the original right back
+        settling: VecDeque<(Instant, Move)>,
+    }
+
+    struct Move {
+        from: PathBuf,
+        to: PathBuf,
+        is_dir: bool,
+    }
+
+    /// Backup and swap names editors rename files to while saving.
+    fn is_backup(path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        let extension = Path::new(name).extension().and_then(|ext| ext.to_str());
+        name.ends_with('~')
+            || name.starts_with(".#")
+            || matches!(extension, Some("swp" | "swx" | "bak" | "tmp"))
+    }
+
+    impl Watcher {
+        /// Watches the directories of every tracked file, and the directories above
+        /// them so renaming a whole directory is noticed too.
+        fn watch_entries(&mut self, texoxide: &Texoxide) -> Result<()> {
+            let mut failed = None;
+            for entry in texoxide.entries()? {
+                if let Err(e) = self.watch_ancestors(Path::new(&entry.path)) {
+                    failed = Some(e);
+                }
+            }
+            if let Some(e) = failed {
+                eprintln!("Some directories can't be watched: {e}");
+            }
+            Ok(())
+        }
+
+        fn watch_ancestors(&mut self, path: &Path) -> io::Result<()> {
+            // The root itself isn't watched, every move on the system would show up
+            for dir in path
+                .ancestors()
+                .skip(1)
+                .filter(|dir| dir.parent().is_some())
+            {
+                if self.watched.contains_key(dir) {
+                    break;
+                }
+                match self.watches.add(dir, WatchMask::MOVE | WatchMask::ONLYDIR) {
+                    Ok(wd) => {
+                        self.dirs.insert(wd.clone(), dir.to_path_buf());
+                        self.watched.insert(dir.to_path_buf(), wd);
+                    }
+                    Err(e) if e.kind() == ErrorKind::NotFound => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        }
+
+        /// Watches keep following a directory after it's renamed, only the paths we
+        /// know them by have to change.
+        fn rename_dirs(&mut self, from: &Path, to: &Path) {
+            for dir in self.dirs.values_mut() {
+                if let Ok(rest) = dir.strip_prefix(from) {
+                    let renamed = to.join(rest);
+                    if let Some(wd) = self.watched.remove(dir) {
+                        self.watched.insert(renamed.clone(), wd);
+                    }
+                    *dir = renamed;
+                }
+            }
+        }
+
+        fn handle(&mut self, texoxide: &Texoxide, event: EventOwned) -> Result<()> {
+            if event.mask.contains(EventMask::IGNORED) {
+                if let Some(dir) = self.dirs.remove(&event.wd) {
+                    self.watched.remove(&dir);
+                }
+                return Ok(());
+            }
+            let (Some(dir), Some(name)) = (self.dirs.get(&event.wd), event.name) else {
+                return Ok(());
+            };
+            let path = dir.join(name);
+            if event.mask.contains(EventMask::MOVED_FROM) {
+                // Files moved out of the watched directories never get a MOVED_TO
+                if self.pending.len() >= MAX_PENDING {
+                    self.pending.clear();
+                }
+                self.pending.insert(event.cookie, path);
+                return Ok(());
+            }
+            let Some(from) = self.pending.remove(&event.cookie) else {
+                return Ok(());
+            };
+            let moved = Move {
+                from,
+                to: path,
+                is_dir: event.mask.contains(EventMask::ISDIR),
+            };
+            // Later events inside a renamed directory need its new path right away
+            if moved.is_dir {
+                self.apply(texoxide, &moved)
+            } else {
+                self.settling.push_back((Instant::now(), moved));
+                Ok(())
+            }
+        }
+
+        fn apply(&mut self, texoxide: &Texoxide, moved: &Move) -> Result<()> {
+            let (Some(from), Some(to)) = (moved.from.to_str(), moved.to.to_str()) else {
+                return Ok(());
+            };
+            let count = if moved.is_dir {
+                self.rename_dirs(&moved.from, &moved.to);
+                texoxide.relocate_dir(from, to)?
+            } else if moved.from.exists() || is_backup(&moved.to) {
+                0
+            } else {
+                usize::from(texoxide.relocate(from, to)?)
+            };
+
+            if count > 0 {
+                println!("{from} -> {to} ({count} tracked)");
+                if let Err(e) = self.watch_ancestors(&moved.to) {
+                    eprintln!("Cannot watch {}: {e}", moved.to.display());
+                }
+            }
+            Ok(())
+        }
+
+        fn apply_settled(&mut self, texoxide: &Texoxide) -> Result<()> {
+            while self
+                .settling
+                .front()
+                .is_some_and(|(at, _)| at.elapsed() >= SETTLE)
+            {
+                if let Some((_, moved)) = self.settling.pop_front() {
+                    self.apply(texoxide, &moved)?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn read_events(mut inotify: Inotify, sender: &Sender<io::Result<EventOwned>>) {
+        let mut buffer = [0; 4096];
+        loop {
+            match inotify.read_events_blocking(&mut buffer) {
+                Ok(events) => {
+                    for event in events {
+                        if sender.send(Ok(event.to_owned())).is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(e) => {
+                    let _ = sender.send(Err(e));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Follows tracked files and directories that are renamed or moved, until killed.
+    pub fn watch(texoxide: &Texoxide) -> Result<()> {
+        let inotify = Inotify::init().context("Failed to start inotify")?;
+        let mut watcher = Watcher {
+            watches: inotify.watches(),
+            dirs: HashMap::new(),
+            watched: HashMap::new(),
+            pending: HashMap::new(),
+            settling: VecDeque::new(),
+        };
+        watcher.watch_entries(texoxide)?;
+        println!("Watching {} directories", watcher.dirs.len());
+
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || read_events(inotify, &sender));
+
+        let mut scanned = Instant::now();
+        loop {
+            let rescan = scanned + RESCAN_INTERVAL;
+            let wake = watcher
+                .settling
+                .front()
+                .map_or(rescan, |(at, _)| rescan.min(*at + SETTLE));
+            match events.recv_timeout(wake.saturating_duration_since(Instant::now())) {
+                Ok(event) => {
+                    let event = event.context("Failed to read inotify events")?;
+                    watcher.handle(texoxide, event)?;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => anyhow::bail!("inotify stopped"),
+            }
+
+            watcher.apply_settled(texoxide)?;
+            if scanned.elapsed() >= RESCAN_INTERVAL {
+                watcher.watch_entries(texoxide)?;
+                scanned = Instant::now();
+            }
+        }
+    }
+}